@@ -0,0 +1,161 @@
+use std::path::{Path, PathBuf};
+
+use lofty::config::WriteOptions;
+use lofty::file::TaggedFileExt;
+use lofty::probe::Probe;
+use lofty::tag::{Accessor, Tag};
+use thiserror::Error;
+
+/// Marks exported clips as having come from this tool, so a media player's
+/// genre/album fields identify the source even once copied elsewhere.
+const SOURCE_MARKER: &str = "dictionary (dictionaryapi.dev)";
+
+/// Preferred pronunciation accent, selected via `--accent`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, clap::ValueEnum)]
+pub enum Accent {
+    Us,
+    Uk,
+    Any,
+}
+
+/// Preferred audio container/codec, selected via `--format`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, clap::ValueEnum)]
+pub enum Format {
+    Mp3,
+    Ogg,
+    Auto,
+}
+
+/// Builds an ordered list of acceptable `<accent>.<extension>` suffixes,
+/// most preferred first, that a downloaded file name is checked against.
+fn preference_list(accent: Accent, format: Format) -> Vec<String> {
+    let accents: &[&str] = match accent {
+        Accent::Us => &["us"],
+        Accent::Uk => &["uk"],
+        Accent::Any => &["us", "uk"],
+    };
+    let formats: &[&str] = match format {
+        Format::Mp3 => &["mp3"],
+        Format::Ogg => &["ogg"],
+        Format::Auto => &["mp3", "ogg"],
+    };
+
+    accents
+        .iter()
+        .flat_map(|accent| {
+            formats
+                .iter()
+                .map(move |format| format!("{accent}.{format}"))
+        })
+        .collect()
+}
+
+/// Picks the highest-priority download matching `accent`/`format`, falling
+/// back through the rest of the preference list when it's missing.
+pub fn pick_preferred(
+    downloads: &[(String, PathBuf)],
+    accent: Accent,
+    format: Format,
+) -> Option<(String, PathBuf)> {
+    preference_list(accent, format)
+        .into_iter()
+        .find_map(|suffix| {
+            downloads
+                .iter()
+                .find(|(file_name, _)| file_name.ends_with(&suffix))
+                .cloned()
+        })
+}
+
+#[derive(Error, Debug)]
+pub enum SaveError {
+    #[error("io error")]
+    IoError(#[from] std::io::Error),
+
+    #[error("tag error")]
+    LoftyError(#[from] lofty::error::LoftyError),
+}
+
+/// Copies the downloaded pronunciation at `source` to `dest` and embeds
+/// ID3/Vorbis tags (whichever the container uses) identifying the word, its
+/// phonetic transcription and this tool as the source, so the exported clip
+/// is self-describing in a media player.
+pub fn save_tagged(
+    source: &Path,
+    dest: &Path,
+    word: &str,
+    phonetic_text: Option<&str>,
+) -> Result<(), SaveError> {
+    std::fs::copy(source, dest)?;
+
+    let mut tagged_file = Probe::open(dest)?.read()?;
+    if tagged_file.primary_tag().is_none() {
+        let tag_type = tagged_file.primary_tag_type();
+        tagged_file.insert_tag(Tag::new(tag_type));
+    }
+    let tag = tagged_file
+        .primary_tag_mut()
+        .expect("tag was just inserted if missing");
+
+    tag.set_title(word.to_owned());
+    if let Some(phonetic_text) = phonetic_text {
+        tag.set_artist(phonetic_text.to_owned());
+        tag.set_comment(phonetic_text.to_owned());
+    }
+    tag.set_genre(SOURCE_MARKER.to_owned());
+    tag.set_album(SOURCE_MARKER.to_owned());
+
+    tag.save_to_path(dest, WriteOptions::default())?;
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn preference_list_orders_any_accent_auto_format_us_before_uk_and_mp3_before_ogg() {
+        assert_eq!(
+            preference_list(Accent::Any, Format::Auto),
+            vec!["us.mp3", "us.ogg", "uk.mp3", "uk.ogg"],
+        );
+    }
+
+    #[test]
+    fn preference_list_narrows_to_a_single_accent_and_format() {
+        assert_eq!(preference_list(Accent::Uk, Format::Ogg), vec!["uk.ogg"]);
+    }
+
+    fn downloads(names: &[&str]) -> Vec<(String, PathBuf)> {
+        names
+            .iter()
+            .map(|name| (name.to_string(), PathBuf::from(name)))
+            .collect()
+    }
+
+    #[test]
+    fn pick_preferred_returns_the_highest_priority_match() {
+        let downloads = downloads(&["word-uk.mp3", "word-us.mp3"]);
+
+        let (file_name, _) = pick_preferred(&downloads, Accent::Any, Format::Auto).unwrap();
+
+        assert_eq!(file_name, "word-us.mp3");
+    }
+
+    #[test]
+    fn pick_preferred_falls_back_through_the_rest_of_the_list_when_us_is_missing() {
+        let downloads = downloads(&["word-uk.mp3"]);
+
+        let (file_name, _) = pick_preferred(&downloads, Accent::Any, Format::Auto).unwrap();
+
+        assert_eq!(file_name, "word-uk.mp3");
+    }
+
+    #[test]
+    fn pick_preferred_returns_none_when_nothing_matches() {
+        let downloads = downloads(&["word-us.mp3"]);
+
+        assert!(pick_preferred(&downloads, Accent::Uk, Format::Ogg).is_none());
+    }
+}
@@ -0,0 +1,74 @@
+use std::path::PathBuf;
+
+use tempfile::NamedTempFile;
+use thiserror::Error;
+
+#[derive(Error, Debug)]
+pub enum CacheError {
+    #[error("sled error")]
+    SledError(#[from] sled::Error),
+
+    #[error("io error")]
+    IoError(#[from] std::io::Error),
+
+    #[error("persist error")]
+    PersistError(#[from] tempfile::PersistError),
+}
+
+/// Content-addressed cache for downloaded pronunciation audio.
+///
+/// Audio bytes live on disk under `dirs::cache_dir()/dictionary`, named by
+/// the SHA-256 hex digest of their contents, while a sled tree maps each
+/// source URL to the digest it resolved to. This lets repeated lookups of
+/// the same word skip the network entirely.
+pub struct AudioCache {
+    dir: PathBuf,
+    urls: sled::Tree,
+}
+
+impl AudioCache {
+    pub fn open() -> Result<Self, CacheError> {
+        let dir = dirs::cache_dir()
+            .expect("could not determine platform cache directory")
+            .join("dictionary");
+        std::fs::create_dir_all(&dir)?;
+
+        let db = sled::open(dir.join("index.sled"))?;
+        let urls = db.open_tree("url_to_hash")?;
+
+        Ok(Self { dir, urls })
+    }
+
+    /// Returns the path of a previously-cached download for `url`, if any.
+    pub fn lookup(&self, url: &str) -> Result<Option<PathBuf>, CacheError> {
+        let Some(hash) = self.urls.get(url)? else {
+            return Ok(None);
+        };
+        let path = self.dir.join(String::from_utf8_lossy(&hash).as_ref());
+        Ok(path.exists().then_some(path))
+    }
+
+    /// Allocates a temp file inside the cache directory to stream a download into.
+    pub fn temp_file(&self) -> Result<NamedTempFile, CacheError> {
+        Ok(NamedTempFile::new_in(&self.dir)?)
+    }
+
+    /// Persists a fully-downloaded temp file under its content hash and
+    /// records the URL -> hash mapping, so future lookups hit the cache.
+    /// Renaming only happens once the caller has hashed the complete stream,
+    /// so a partial download never corrupts the cache.
+    pub fn finalize(
+        &self,
+        url: &str,
+        hash: &str,
+        tmp_file: NamedTempFile,
+    ) -> Result<PathBuf, CacheError> {
+        let dest = self.dir.join(hash);
+        if !dest.exists() {
+            tmp_file.persist(&dest)?;
+        }
+        self.urls.insert(url, hash.as_bytes())?;
+        self.urls.flush()?;
+        Ok(dest)
+    }
+}
@@ -0,0 +1,99 @@
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
+
+use crate::Word;
+
+#[derive(Error, Debug)]
+pub enum HistoryError {
+    #[error("sled error")]
+    SledError(#[from] sled::Error),
+
+    #[error("io error")]
+    IoError(#[from] std::io::Error),
+
+    #[error("serialization error")]
+    SerdeError(#[from] serde_json::Error),
+}
+
+/// A single past lookup: the parsed API response plus enough metadata to
+/// list, search and re-render it without another network round-trip.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct HistoryEntry {
+    pub word: String,
+    pub looked_up_at: u64,
+    pub favorite: bool,
+    pub words: Vec<Word>,
+}
+
+/// Persistent store of past lookups, keyed by word, backed by a sled tree
+/// under the user's data dir.
+pub struct History {
+    tree: sled::Tree,
+}
+
+impl History {
+    pub fn open() -> Result<Self, HistoryError> {
+        let dir = dirs::data_dir()
+            .expect("could not determine platform data directory")
+            .join("dictionary");
+        std::fs::create_dir_all(&dir)?;
+
+        let db = sled::open(dir.join("history.sled"))?;
+        let tree = db.open_tree("lookups")?;
+
+        Ok(Self { tree })
+    }
+
+    /// Records (or overwrites) the lookup for `word`, timestamped now.
+    pub fn record(&self, word: &str, words: Vec<Word>, favorite: bool) -> Result<(), HistoryError> {
+        let looked_up_at = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or_default()
+            .as_secs();
+
+        let entry = HistoryEntry {
+            word: word.to_owned(),
+            looked_up_at,
+            favorite,
+            words,
+        };
+
+        self.tree.insert(word, serde_json::to_vec(&entry)?)?;
+        self.tree.flush()?;
+        Ok(())
+    }
+
+    pub fn get(&self, word: &str) -> Result<Option<HistoryEntry>, HistoryError> {
+        match self.tree.get(word)? {
+            Some(bytes) => Ok(Some(serde_json::from_slice(&bytes)?)),
+            None => Ok(None),
+        }
+    }
+
+    /// Removes `word` from history, reporting whether an entry existed.
+    pub fn forget(&self, word: &str) -> Result<bool, HistoryError> {
+        let removed = self.tree.remove(word)?;
+        self.tree.flush()?;
+        Ok(removed.is_some())
+    }
+
+    /// Lists all entries, optionally filtered to words containing `query`,
+    /// oldest lookup first.
+    pub fn list(&self, query: Option<&str>) -> Result<Vec<HistoryEntry>, HistoryError> {
+        let mut entries = self
+            .tree
+            .iter()
+            .values()
+            .map(|result| Ok(serde_json::from_slice::<HistoryEntry>(&result?)?))
+            .collect::<Result<Vec<_>, HistoryError>>()?;
+
+        if let Some(query) = query {
+            entries.retain(|entry| entry.word.contains(query));
+        }
+
+        entries.sort_by_key(|entry| entry.looked_up_at);
+        Ok(entries)
+    }
+}
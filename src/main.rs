@@ -1,12 +1,80 @@
+use bytes::Bytes;
+use clap::{Parser, Subcommand};
 use futures::StreamExt;
-use std::{collections::HashSet, env, io::BufReader, path::PathBuf};
-use tempfile::TempDir;
+use std::{
+    collections::{HashMap, HashSet},
+    io::BufReader,
+    io::Write,
+    path::PathBuf,
+};
 
 use serde::{Deserialize, Serialize};
 
 use askama::Template;
+use sha2::{Digest, Sha256};
 use thiserror::Error;
 
+mod audio;
+mod cache;
+mod history;
+mod retry;
+
+use cache::AudioCache;
+use retry::RetryConfig;
+
+/// Look up a word's definitions and pronunciation.
+///
+/// If the word happens to collide with a subcommand name (`history`,
+/// `show`, `forget`), separate it with `--` so it's parsed as the word
+/// rather than dispatched as a subcommand, e.g. `dictionary -- show`.
+#[derive(Parser, Debug)]
+#[command(version, about)]
+struct Cli {
+    /// The word to look up (omit when using a subcommand; use `--` before
+    /// it if it collides with a subcommand name, e.g. `dictionary -- show`)
+    word: Option<String>,
+
+    /// Preferred pronunciation accent
+    #[arg(long, value_enum, default_value = "any")]
+    accent: audio::Accent,
+
+    /// Preferred audio container/codec
+    #[arg(long, value_enum, default_value = "auto")]
+    format: audio::Format,
+
+    /// Save the chosen pronunciation to this path, tagged with the word and
+    /// its phonetic transcription, in addition to playing it (see --no-play)
+    #[arg(long)]
+    save: Option<PathBuf>,
+
+    /// Don't play the pronunciation through rodio (useful with --save)
+    #[arg(long)]
+    no_play: bool,
+
+    /// Pin this lookup as a favorite in history
+    #[arg(long)]
+    favorite: bool,
+
+    /// Max attempts for the dictionary API and audio requests before giving up
+    #[arg(long, default_value_t = 5)]
+    retries: u32,
+
+    #[command(subcommand)]
+    command: Option<Command>,
+}
+
+#[derive(Subcommand, Debug)]
+enum Command {
+    /// List past lookups, optionally filtered to words containing a substring
+    History { query: Option<String> },
+
+    /// Re-render a past lookup fully offline, without another network call
+    Show { word: String },
+
+    /// Remove a word from history
+    Forget { word: String },
+}
+
 #[derive(Debug, Serialize, Deserialize)]
 struct Phonetic {
     text: Option<String>,
@@ -41,23 +109,99 @@ enum DownloadError {
 
     #[error("io error")]
     IoError(#[from] std::io::Error),
+
+    #[error("cache error")]
+    CacheError(#[from] cache::CacheError),
+
+    #[error("stream ended after {received} of {expected} expected bytes")]
+    TruncatedStream { expected: u64, received: u64 },
+}
+
+impl DownloadError {
+    /// Connection failures, 5xx responses and streams that cut off early are
+    /// all worth a retry; a corrupt cache or a malformed URL is not.
+    fn is_retryable(&self) -> bool {
+        match self {
+            DownloadError::ReqwestError(err) => {
+                retry::is_retryable_reqwest_error(err) || err.is_body()
+            }
+            DownloadError::TruncatedStream { .. } => true,
+            DownloadError::IoError(_) | DownloadError::CacheError(_) => false,
+        }
+    }
+}
+
+/// Streams a single download attempt into a fresh cache temp file, hashing
+/// as it goes. The response body is relayed through a bounded channel so a
+/// mid-stream disconnect (short read vs. `Content-Length`) is detected here
+/// rather than silently producing a truncated file.
+async fn fetch_audio_once(
+    url: &str,
+    cache: &AudioCache,
+) -> Result<(String, tempfile::NamedTempFile), DownloadError> {
+    let response = reqwest::get(url).await?.error_for_status()?;
+    let expected_len = response.content_length();
+    let mut byte_stream = response.bytes_stream();
+
+    let (tx, mut rx) = tokio::sync::mpsc::channel::<Result<Bytes, reqwest::Error>>(16);
+    let producer = tokio::spawn(async move {
+        while let Some(item) = byte_stream.next().await {
+            if tx.send(item).await.is_err() {
+                break;
+            }
+        }
+    });
+
+    let mut tmp_file = cache.temp_file()?;
+    let mut hasher = Sha256::new();
+    let mut received = 0u64;
+
+    while let Some(item) = rx.recv().await {
+        let bytes = item?;
+        received += bytes.len() as u64;
+        hasher.update(&bytes);
+        tmp_file.write_all(&bytes)?;
+    }
+
+    let _ = producer.await;
+
+    if let Some(expected) = expected_len {
+        if received != expected {
+            return Err(DownloadError::TruncatedStream { expected, received });
+        }
+    }
+
+    Ok((format!("{:x}", hasher.finalize()), tmp_file))
+}
+
+/// The last path segment of a URL, used as the on-disk and display name for
+/// a downloaded pronunciation.
+fn file_name_from_url(url: &str) -> String {
+    url.split("/")
+        .last()
+        .expect("No file name found in url")
+        .to_owned()
 }
 
 async fn download_audio_file(
     url: String,
-    temp_dir: &TempDir,
+    cache: &AudioCache,
+    retry_config: &RetryConfig,
 ) -> Result<(String, PathBuf), DownloadError> {
-    let file_name = url.split("/").last().expect("No file name found in url");
-    let file_path = temp_dir.path().join(file_name);
+    let file_name = file_name_from_url(&url);
 
-    let mut tmp_file = tokio::fs::File::create(&file_path).await?;
-    let mut byte_stream = reqwest::get(&url).await?.bytes_stream();
-
-    while let Some(item) = byte_stream.next().await {
-        tokio::io::copy(&mut item?.as_ref(), &mut tmp_file).await?;
+    if let Some(cached_path) = cache.lookup(&url)? {
+        return Ok((file_name, cached_path));
     }
 
-    Ok((file_name.to_owned(), file_path))
+    let (hash, tmp_file) = retry::with_backoff(retry_config, DownloadError::is_retryable, || {
+        fetch_audio_once(&url, cache)
+    })
+    .await?;
+
+    let file_path = cache.finalize(&url, &hash, tmp_file)?;
+
+    Ok((file_name, file_path))
 }
 
 #[derive(Template)]
@@ -66,65 +210,207 @@ struct WordTemplate<'a> {
     words: &'a Vec<Word>,
 }
 
+/// Opens the audio cache, exiting with an actionable message rather than
+/// panicking with a raw sled error if e.g. another instance already holds
+/// its lock.
+fn open_audio_cache() -> AudioCache {
+    AudioCache::open().unwrap_or_else(|err| {
+        eprintln!(
+            "Error: failed to open audio cache ({err}); is another instance of dictionary already running?"
+        );
+        std::process::exit(1);
+    })
+}
+
+fn print_markdown(markdown: &str) {
+    bat::PrettyPrinter::new()
+        .input_from_bytes(markdown.as_bytes())
+        .grid(false)
+        .header(false)
+        .line_numbers(false)
+        .paging_mode(bat::PagingMode::Always)
+        .language("md")
+        .print()
+        .unwrap();
+}
+
+/// Opens the history store, exiting with an actionable message rather than
+/// panicking with a raw sled error if e.g. another instance already holds
+/// its lock.
+fn open_history() -> history::History {
+    history::History::open().unwrap_or_else(|err| {
+        eprintln!(
+            "Error: failed to open history store ({err}); is another instance of dictionary already running?"
+        );
+        std::process::exit(1);
+    })
+}
+
+fn run_history(query: Option<&str>) {
+    let history = open_history();
+    let entries = history.list(query).expect("Failed to read history");
+
+    for entry in entries {
+        let marker = if entry.favorite { "*" } else { " " };
+        println!("{marker} {}", entry.word);
+    }
+}
+
+fn run_show(word: &str) {
+    let history = open_history();
+    let entry = history
+        .get(word)
+        .expect("Failed to read history")
+        .unwrap_or_else(|| panic!("no cached lookup for \"{word}\""));
+
+    let word_page = WordTemplate {
+        words: &entry.words,
+    };
+    print_markdown(&word_page.render().unwrap());
+}
+
+fn run_forget(word: &str) {
+    let history = open_history();
+    if history.forget(word).expect("Failed to update history") {
+        println!("Forgot \"{word}\"");
+    } else {
+        println!("No history entry for \"{word}\"");
+    }
+}
+
 #[tokio::main]
 async fn main() -> Result<(), reqwest::Error> {
+    let cli = Cli::parse();
+
+    match &cli.command {
+        Some(Command::History { query }) => {
+            run_history(query.as_deref());
+            return Ok(());
+        }
+        Some(Command::Show { word }) => {
+            run_show(word);
+            return Ok(());
+        }
+        Some(Command::Forget { word }) => {
+            run_forget(word);
+            return Ok(());
+        }
+        None => {}
+    }
+
     let client = reqwest::Client::new();
+    let word = cli.word.clone().expect("word not provided");
+    let retry_config = RetryConfig::with_max_attempts(cli.retries);
 
-    let word = env::args().skip(1).next().expect("word not provided");
-    let words: Vec<Word> = client
-        .get(format!(
-            "https://api.dictionaryapi.dev/api/v2/entries/en/{}",
-            word
-        ))
-        .send()
-        .await?
-        .json()
+    let response =
+        retry::with_backoff(&retry_config, retry::is_retryable_reqwest_error, || async {
+            client
+                .get(format!(
+                    "https://api.dictionaryapi.dev/api/v2/entries/en/{}",
+                    word
+                ))
+                .send()
+                .await?
+                .error_for_status()
+        })
         .await?;
 
+    let words: Vec<Word> = response.json().await?;
+
     let all_audio_url = words
         .iter()
         .flat_map(|word| word.phonetics.iter())
         .map(|phonetic| phonetic.audio.clone())
         .collect::<HashSet<String>>();
 
+    let phonetic_text_by_file_name = words
+        .iter()
+        .flat_map(|word| word.phonetics.iter())
+        .map(|phonetic| {
+            let file_name = file_name_from_url(&phonetic.audio);
+            (file_name, phonetic.text.clone())
+        })
+        .collect::<HashMap<String, Option<String>>>();
+
     let word_page = WordTemplate { words: &words };
 
     let page = word_page.render().unwrap();
 
-    tokio::spawn(async move {
-        let temp_dir = tempfile::tempdir().expect("Failed to create temp dir");
+    let history = open_history();
+    let was_favorite = history
+        .get(&word)
+        .expect("Failed to read history")
+        .map(|entry| entry.favorite)
+        .unwrap_or(false);
+    history
+        .record(&word, words, cli.favorite || was_favorite)
+        .expect("Failed to record history entry");
+
+    let accent = cli.accent;
+    let format = cli.format;
+    let save_path = cli.save.clone();
+    let no_play = cli.no_play;
+    let retries = cli.retries;
+    let word = word.clone();
+
+    let download_task: tokio::task::JoinHandle<Result<(), String>> = tokio::spawn(async move {
+        let cache = open_audio_cache();
+        let retry_config = RetryConfig::with_max_attempts(retries);
         let futures = all_audio_url
             .into_iter()
-            .map(|url| download_audio_file(url.clone(), &temp_dir));
+            .map(|url| download_audio_file(url.clone(), &cache, &retry_config));
 
-        let audio = futures::future::join_all(futures)
+        let downloads = futures::future::join_all(futures)
             .await
             .into_iter()
-            .filter(|output| output.is_ok())
-            .map(|output| output.unwrap())
-            .filter(|(file_name, _)| file_name.ends_with("us.mp3"))
-            .next();
-
-        if let Some((_, file_path)) = audio {
-            use rodio::{source::Source, Decoder, OutputStream};
-            let (_stream, stream_handle) = OutputStream::try_default().unwrap();
-            let file = BufReader::new(std::fs::File::open(file_path).unwrap());
-            let source = Decoder::new(file).unwrap();
-            stream_handle.play_raw(source.convert_samples()).unwrap();
-
-            std::thread::sleep(std::time::Duration::from_secs(1));
+            .filter_map(|output| match output {
+                Ok(download) => Some(download),
+                Err(err) => {
+                    eprintln!("Failed to download pronunciation: {err}");
+                    None
+                }
+            })
+            .collect::<Vec<_>>();
+
+        let chosen_file = audio::pick_preferred(&downloads, accent, format);
+
+        if let Some(save_path) = &save_path {
+            let Some((file_name, file_path)) = &chosen_file else {
+                return Err("no pronunciation audio available to save".to_owned());
+            };
+
+            let phonetic_text = phonetic_text_by_file_name
+                .get(file_name)
+                .and_then(|text| text.as_deref());
+            audio::save_tagged(file_path, save_path, &word, phonetic_text)
+                .map_err(|err| format!("failed to save pronunciation: {err}"))?;
+        }
+
+        if !no_play {
+            if let Some((_, file_path)) = &chosen_file {
+                use rodio::{source::Source, Decoder, OutputStream};
+                let (_stream, stream_handle) = OutputStream::try_default().unwrap();
+                let file = BufReader::new(std::fs::File::open(file_path).unwrap());
+                let source = Decoder::new(file).unwrap();
+                stream_handle.play_raw(source.convert_samples()).unwrap();
+
+                std::thread::sleep(std::time::Duration::from_secs(1));
+            }
         }
+
+        Ok(())
     });
 
-    bat::PrettyPrinter::new()
-        .input_from_bytes(page.as_bytes())
-        .grid(false)
-        .header(false)
-        .line_numbers(false)
-        .paging_mode(bat::PagingMode::Always)
-        .language("md")
-        .print()
-        .unwrap();
+    print_markdown(&page);
+
+    match download_task.await {
+        Ok(Ok(())) => {}
+        Ok(Err(message)) => {
+            eprintln!("Error: {message}");
+            std::process::exit(1);
+        }
+        Err(_) => {}
+    }
 
     Ok(())
 }
@@ -0,0 +1,144 @@
+use std::future::Future;
+use std::time::Duration;
+
+/// Exponential backoff schedule: start at `initial_delay` and double after
+/// each failed attempt, for up to `max_attempts` tries total (at least one
+/// try is always made, even when `max_attempts` is 0).
+#[derive(Debug, Clone, Copy)]
+pub struct RetryConfig {
+    pub max_attempts: u32,
+    pub initial_delay: Duration,
+}
+
+impl Default for RetryConfig {
+    fn default() -> Self {
+        Self {
+            max_attempts: 5,
+            initial_delay: Duration::from_secs(1),
+        }
+    }
+}
+
+impl RetryConfig {
+    /// A `RetryConfig` with the default initial delay but a caller-chosen
+    /// attempt budget, e.g. from a `--retries` flag.
+    pub fn with_max_attempts(max_attempts: u32) -> Self {
+        Self {
+            max_attempts,
+            ..Self::default()
+        }
+    }
+}
+
+/// Runs `attempt` until it succeeds, `should_retry` rejects the error, or
+/// `max_attempts` is exhausted, sleeping with doubling delay between tries.
+pub async fn with_backoff<T, E, F, Fut>(
+    config: &RetryConfig,
+    should_retry: impl Fn(&E) -> bool,
+    mut attempt: F,
+) -> Result<T, E>
+where
+    F: FnMut() -> Fut,
+    Fut: Future<Output = Result<T, E>>,
+{
+    let mut delay = config.initial_delay;
+    let mut attempts_left = config.max_attempts;
+
+    loop {
+        match attempt().await {
+            Ok(value) => return Ok(value),
+            Err(err) => {
+                attempts_left = attempts_left.saturating_sub(1);
+                if attempts_left == 0 || !should_retry(&err) {
+                    return Err(err);
+                }
+                tokio::time::sleep(delay).await;
+                delay *= 2;
+            }
+        }
+    }
+}
+
+/// Whether a `reqwest::Error` looks transient and worth retrying: connection
+/// failures, timeouts, and 5xx responses.
+pub fn is_retryable_reqwest_error(err: &reqwest::Error) -> bool {
+    err.is_connect()
+        || err.is_timeout()
+        || err
+            .status()
+            .map(|status| status.is_server_error())
+            .unwrap_or(false)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::cell::Cell;
+
+    fn instant(max_attempts: u32) -> RetryConfig {
+        RetryConfig {
+            max_attempts,
+            initial_delay: Duration::from_millis(0),
+        }
+    }
+
+    #[tokio::test]
+    async fn with_backoff_gives_up_after_max_attempts() {
+        let attempts = Cell::new(0);
+
+        let result = with_backoff(
+            &instant(3),
+            |_: &&str| true,
+            || {
+                attempts.set(attempts.get() + 1);
+                async { Err("always fails") }
+            },
+        )
+        .await;
+
+        assert_eq!(result, Err("always fails"));
+        assert_eq!(attempts.get(), 3);
+    }
+
+    #[tokio::test]
+    async fn with_backoff_stops_immediately_when_the_error_is_not_retryable() {
+        let attempts = Cell::new(0);
+
+        let result = with_backoff(
+            &instant(5),
+            |_: &&str| false,
+            || {
+                attempts.set(attempts.get() + 1);
+                async { Err("permanent failure") }
+            },
+        )
+        .await;
+
+        assert_eq!(result, Err("permanent failure"));
+        assert_eq!(attempts.get(), 1);
+    }
+
+    #[tokio::test]
+    async fn with_backoff_returns_as_soon_as_an_attempt_succeeds() {
+        let attempts = Cell::new(0);
+
+        let result = with_backoff(
+            &instant(5),
+            |_: &&str| true,
+            || {
+                attempts.set(attempts.get() + 1);
+                async move {
+                    if attempts.get() < 2 {
+                        Err("not yet")
+                    } else {
+                        Ok("done")
+                    }
+                }
+            },
+        )
+        .await;
+
+        assert_eq!(result, Ok("done"));
+        assert_eq!(attempts.get(), 2);
+    }
+}